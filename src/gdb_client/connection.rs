@@ -0,0 +1,455 @@
+//! Splits the connection surface in two: a half that blocks for each reply,
+//! and a half that doesn't. Both decode wire bytes the same way, by reusing
+//! [`parser::frame`]/[`parser::packet_body`], so that logic stays
+//! single-sourced between them.
+//!
+//! The split matters for long-running `vCont` continuations: the async half
+//! lets `%Stop` notifications arrive unsolicited while the client is also
+//! free to issue other commands, which a single blocking `send`/`recv` can't
+//! express.
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use super::parser::{self, Frame};
+
+/// Reads and writes raw bytes to the wire. Blocking: a call only returns once
+/// it has made progress.
+pub trait RawIo {
+    type Error: std::error::Error + 'static;
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+    /// Read whatever bytes are currently available, blocking until there are
+    /// some (or the connection is closed, in which case return `Ok(0)`).
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+/// Sends a packet and blocks until it has read, checksum-verified, and
+/// [`parser::expand_body`]-decoded the reply.
+pub trait SyncConnection {
+    type Error: std::error::Error + 'static;
+
+    fn send_packet(&mut self, body: &[u8]) -> Result<Vec<u8>, Self::Error>;
+}
+
+/// Sends a packet without waiting for the reply, and surfaces replies and
+/// `%`-notifications as they arrive off the wire.
+pub trait AsyncConnection {
+    type Error: std::error::Error + 'static;
+    type Reply: Future<Output = Result<Vec<u8>, Self::Error>>;
+
+    /// Queue `body` for transmission, returning a future that resolves once
+    /// its reply has arrived. Does not block waiting for that reply.
+    fn send_packet(&mut self, body: &[u8]) -> Result<Self::Reply, Self::Error>;
+
+    /// Pop the next unsolicited `%`-notification body that has arrived,
+    /// without blocking. Returns `None` if none is queued.
+    fn poll_notification(&mut self) -> Result<Option<Vec<u8>>, Self::Error>;
+}
+
+/// Drives a [`RawIo`] as a [`SyncConnection`], resending on `-` (nack) and
+/// reading more on a short read that doesn't yet contain a full packet.
+pub struct GdbConnection<T> {
+    io: T,
+    buf: Vec<u8>,
+    max_retries: u32,
+}
+
+impl<T> GdbConnection<T> {
+    pub fn new(io: T) -> Self {
+        Self {
+            io,
+            buf: Vec::new(),
+            max_retries: DEFAULT_MAX_RETRIES,
+        }
+    }
+}
+
+const DEFAULT_MAX_RETRIES: u32 = 5;
+/// How many bytes to read from the wire at a time while waiting for a full
+/// frame to arrive.
+const READ_CHUNK_SIZE: usize = 4096;
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum GdbConnectionError<E: std::error::Error> {
+    /// io error: {0}
+    Io(E),
+    /// failed to parse reply: {0}
+    Parse(parser::Error),
+    /// connection closed mid-reply
+    ConnectionClosed,
+    /// gave up after {0} retries
+    RetriesExhausted(u32),
+}
+
+impl<T> SyncConnection for GdbConnection<T>
+where
+    T: RawIo,
+{
+    type Error = GdbConnectionError<T::Error>;
+
+    fn send_packet(&mut self, body: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        let packet = parser::encode_packet(body);
+
+        // One initial send plus up to `max_retries` resends.
+        for _ in 0..=self.max_retries {
+            self.io
+                .write_all(&packet)
+                .map_err(GdbConnectionError::Io)?;
+
+            // Only a `-` means our send needs to be retried. An `Ack`, a
+            // unsolicited notification, or an out-of-band interrupt aren't a
+            // reply to what we just sent: keep reading frames without
+            // rewriting the packet.
+            loop {
+                match self.read_frame()? {
+                    Frame::Packet(body) => return Ok(body),
+                    Frame::Nack => break,
+                    Frame::Ack | Frame::Interrupt | Frame::Notification(_) => continue,
+                }
+            }
+        }
+
+        Err(GdbConnectionError::RetriesExhausted(self.max_retries))
+    }
+}
+
+impl<T> GdbConnection<T>
+where
+    T: RawIo,
+{
+    /// Read from the wire until a full [`Frame`] can be parsed out of `buf`,
+    /// then drain those bytes back out of it.
+    fn read_frame(&mut self) -> Result<Frame, GdbConnectionError<T::Error>> {
+        loop {
+            match parser::frame(&self.buf) {
+                Ok((rest, frame)) => {
+                    let consumed = self.buf.len() - rest.len();
+                    self.buf.drain(..consumed);
+                    return Ok(frame);
+                }
+                // Not enough bytes yet: read more and try again.
+                Err(nom::Err::Incomplete(_)) => {}
+                // A malformed frame: drop the buffered bytes and report it.
+                Err(nom::Err::Error(err) | nom::Err::Failure(err)) => {
+                    self.buf.clear();
+                    return Err(GdbConnectionError::Parse(err));
+                }
+            }
+
+            let mut chunk = [0_u8; READ_CHUNK_SIZE];
+            let read = self
+                .io
+                .read(&mut chunk)
+                .map_err(GdbConnectionError::Io)?;
+            if read == 0 {
+                return Err(GdbConnectionError::ConnectionClosed);
+            }
+            self.buf.extend_from_slice(&chunk[..read]);
+        }
+    }
+}
+
+/// Reads and writes raw bytes to the wire without blocking.
+pub trait NonBlockingIo {
+    type Error: std::error::Error + 'static;
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+    /// Read whatever bytes are currently available without blocking.
+    /// `Ok(0)` means nothing is ready yet, not necessarily that the
+    /// connection is closed.
+    fn try_read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+/// Drives a [`NonBlockingIo`] as an [`AsyncConnection`]. Replies and
+/// notifications are demultiplexed out of the same byte stream by
+/// [`parser::frame`], so the state they're demuxed into (`buf` and the two
+/// queues) is shared between a [`ReplyFuture`] and the connection itself via
+/// `Rc<RefCell<_>>` — there's no reactor here, so a pending [`ReplyFuture`]
+/// re-wakes itself and polls the IO directly rather than waiting on an
+/// external wakeup.
+pub struct AsyncGdbConnection<T> {
+    shared: Rc<RefCell<AsyncGdbConnectionShared<T>>>,
+}
+
+struct AsyncGdbConnectionShared<T> {
+    io: T,
+    buf: Vec<u8>,
+    replies: VecDeque<Vec<u8>>,
+    notifications: VecDeque<Vec<u8>>,
+}
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum AsyncGdbConnectionError<E: std::error::Error> {
+    /// io error: {0}
+    Io(E),
+    /// failed to parse reply: {0}
+    Parse(parser::Error),
+}
+
+impl<T> AsyncGdbConnection<T> {
+    pub fn new(io: T) -> Self {
+        Self {
+            shared: Rc::new(RefCell::new(AsyncGdbConnectionShared {
+                io,
+                buf: Vec::new(),
+                replies: VecDeque::new(),
+                notifications: VecDeque::new(),
+            })),
+        }
+    }
+}
+
+impl<T> AsyncGdbConnectionShared<T>
+where
+    T: NonBlockingIo,
+{
+    /// Pull in whatever bytes are currently available and classify every
+    /// full [`Frame`] they contain into `replies`/`notifications`. `Ack`,
+    /// `Nack` and `Interrupt` carry nothing further to demux here, so
+    /// they're dropped.
+    fn drain_into_queues(&mut self) -> Result<(), AsyncGdbConnectionError<T::Error>> {
+        let mut chunk = [0_u8; READ_CHUNK_SIZE];
+        loop {
+            let read = self
+                .io
+                .try_read(&mut chunk)
+                .map_err(AsyncGdbConnectionError::Io)?;
+            if read == 0 {
+                break;
+            }
+            self.buf.extend_from_slice(&chunk[..read]);
+        }
+
+        loop {
+            match parser::frame(&self.buf) {
+                Ok((rest, frame)) => {
+                    let consumed = self.buf.len() - rest.len();
+                    self.buf.drain(..consumed);
+                    match frame {
+                        Frame::Packet(body) => self.replies.push_back(body),
+                        Frame::Notification(body) => self.notifications.push_back(body),
+                        Frame::Ack | Frame::Nack | Frame::Interrupt => {}
+                    }
+                }
+                // Not enough bytes yet: stop until more arrive.
+                Err(nom::Err::Incomplete(_)) => break,
+                Err(nom::Err::Error(err) | nom::Err::Failure(err)) => {
+                    self.buf.clear();
+                    return Err(AsyncGdbConnectionError::Parse(err));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> AsyncConnection for AsyncGdbConnection<T>
+where
+    T: NonBlockingIo,
+{
+    type Error = AsyncGdbConnectionError<T::Error>;
+    type Reply = ReplyFuture<T>;
+
+    fn send_packet(&mut self, body: &[u8]) -> Result<Self::Reply, Self::Error> {
+        let packet = parser::encode_packet(body);
+        self.shared
+            .borrow_mut()
+            .io
+            .write_all(&packet)
+            .map_err(AsyncGdbConnectionError::Io)?;
+        Ok(ReplyFuture {
+            shared: self.shared.clone(),
+        })
+    }
+
+    fn poll_notification(&mut self) -> Result<Option<Vec<u8>>, Self::Error> {
+        let mut shared = self.shared.borrow_mut();
+        shared.drain_into_queues()?;
+        Ok(shared.notifications.pop_front())
+    }
+}
+
+/// Resolves with the oldest reply queued on its [`AsyncGdbConnection`].
+/// Replies are not tagged with the [`AsyncGdbConnection::send_packet`] call
+/// they answer: with several futures outstanding, whichever is polled first
+/// takes the oldest reply.
+pub struct ReplyFuture<T> {
+    shared: Rc<RefCell<AsyncGdbConnectionShared<T>>>,
+}
+
+impl<T> Future for ReplyFuture<T>
+where
+    T: NonBlockingIo,
+{
+    type Output = Result<Vec<u8>, AsyncGdbConnectionError<T::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = self.shared.borrow_mut();
+        if let Err(err) = shared.drain_into_queues() {
+            return Poll::Ready(Err(err));
+        }
+        match shared.replies.pop_front() {
+            Some(body) => Poll::Ready(Ok(body)),
+            // No reactor to register interest with: ask to be polled again
+            // rather than waiting on a wakeup that would never come.
+            None => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Feeds fixed chunks to `read`/`try_read`, one per call, and records
+    /// every `write_all` call so tests can assert on exactly what (and how
+    /// often) was sent.
+    #[derive(Default)]
+    struct MockIo {
+        reads: VecDeque<Vec<u8>>,
+        writes: Vec<Vec<u8>>,
+    }
+
+    impl MockIo {
+        fn with_reads(reads: impl IntoIterator<Item = &'static [u8]>) -> Self {
+            Self {
+                reads: reads.into_iter().map(|r| r.to_vec()).collect(),
+                writes: Vec::new(),
+            }
+        }
+    }
+
+    impl RawIo for MockIo {
+        type Error = Infallible;
+
+        fn write_all(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+            self.writes.push(bytes.to_vec());
+            Ok(())
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            match self.reads.pop_front() {
+                Some(chunk) => {
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    Ok(chunk.len())
+                }
+                None => Ok(0),
+            }
+        }
+    }
+
+    impl NonBlockingIo for MockIo {
+        type Error = Infallible;
+
+        fn write_all(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+            self.writes.push(bytes.to_vec());
+            Ok(())
+        }
+
+        fn try_read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            match self.reads.pop_front() {
+                Some(chunk) => {
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    Ok(chunk.len())
+                }
+                None => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn send_packet_does_not_resend_on_ack() -> eyre::Result<()> {
+        // A stub that acks before replying: the ack must not be mistaken for
+        // "no reply yet, resend".
+        let io = MockIo::with_reads([&b"+"[..], &b"$foo#44"[..]]);
+        let mut conn = GdbConnection::new(io);
+
+        let reply = conn.send_packet(b"foo")?;
+
+        assert_eq!(b"foo".to_vec(), reply);
+        assert_eq!(1, conn.io.writes.len());
+        Ok(())
+    }
+
+    #[test]
+    fn send_packet_resends_on_nack() -> eyre::Result<()> {
+        let io = MockIo::with_reads([&b"-"[..], &b"$foo#44"[..]]);
+        let mut conn = GdbConnection::new(io);
+
+        let reply = conn.send_packet(b"foo")?;
+
+        assert_eq!(b"foo".to_vec(), reply);
+        assert_eq!(2, conn.io.writes.len());
+        Ok(())
+    }
+
+    #[test]
+    fn send_packet_ignores_interrupt_while_waiting() -> eyre::Result<()> {
+        // The interrupt byte is consumed as its own complete frame, then a
+        // deliberately-malformed notification (wrong checksum) errors out.
+        // If the interrupt had instead triggered a resend, the write count
+        // below would be 2.
+        let io = MockIo::with_reads([&[0x03][..], &b"%foo#00"[..]]);
+        let mut conn = GdbConnection::new(io);
+
+        let result = conn.send_packet(b"foo");
+
+        assert!(result.is_err());
+        assert_eq!(1, conn.io.writes.len());
+        Ok(())
+    }
+
+    #[test]
+    fn async_send_packet_reply_survives_an_interleaved_notification() -> eyre::Result<()> {
+        let io = MockIo::with_reads([&b"%Stop:foo#24"[..], &b"$foo#44"[..]]);
+        let mut conn = AsyncGdbConnection::new(io);
+
+        let reply_future = conn.send_packet(b"foo")?;
+        let reply = block_on(reply_future)?;
+
+        assert_eq!(b"foo".to_vec(), reply);
+        assert_eq!(Some(b"Stop:foo".to_vec()), conn.poll_notification()?);
+        Ok(())
+    }
+
+    /// Drives a future with a waker that just re-polls immediately, since
+    /// none of these mocks ever have a real reactor wake them.
+    fn block_on<F: Future>(mut fut: F) -> F::Output {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+        loop {
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    fn noop_waker() -> std::task::Waker {
+        fn no_op(_: *const ()) {}
+        fn clone(_: *const ()) -> std::task::RawWaker {
+            raw_waker()
+        }
+        fn raw_waker() -> std::task::RawWaker {
+            static VTABLE: std::task::RawWakerVTable =
+                std::task::RawWakerVTable::new(clone, no_op, no_op, no_op);
+            std::task::RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        unsafe { std::task::Waker::from_raw(raw_waker()) }
+    }
+}
@@ -3,44 +3,247 @@ use std::{collections::HashMap, convert::TryInto, fmt};
 use super::common::{self, compute_checksum};
 use nom::{
     branch::alt,
-    bytes::streaming::{tag, take, take_until},
+    // Everything below the packet/notification framing layer (`stop_reply`,
+    // `parse_dict`, `hex_number`, ...) parses an already-fully-materialized
+    // body, so it uses `complete`: `streaming` combinators return `Incomplete`
+    // rather than `Error` when a sub-slice (e.g. one dict value) runs out of
+    // input without a definitive answer, and a standalone `"1"` or `"00"`
+    // always "runs out" at its own end. Only the framing in `frame_ack`/
+    // `frame_nack`/`frame_interrupt`/`framed_payload`/`checksum` genuinely
+    // reads off a growing wire buffer, so those alone use `streaming`.
+    bytes::{
+        complete::{tag, take, take_until},
+        streaming,
+    },
+    combinator::opt,
     error::{context, ContextError, ParseError},
     multi::{many0, many1},
-    sequence::{pair, preceded, tuple},
+    sequence::{preceded, separated_pair, terminated, tuple},
 };
 
 type IResult<I, O> = nom::IResult<I, O, Error>;
 
+/// Parse the full RSP stop-reply grammar: `S`, `T`, `W`, `X`, `w`, `O`, `N`.
+///
+/// See GdbConnection::send_stop_reply_packet
+pub fn stop_reply(i: &[u8]) -> IResult<&[u8], StopReply> {
+    context(
+        "stop_reply",
+        alt((
+            stop_reply_signal,
+            stop_reply_halt,
+            stop_reply_exited,
+            stop_reply_terminated,
+            stop_reply_thread_exited,
+            stop_reply_console_output,
+            stop_reply_no_resumable_threads,
+        )),
+    )(i)
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum StopReply {
+    /// `S AA`
+    Signal { signal_num: u8 },
+    /// `T AA key:value;...`
+    Stop(HaltReason),
+    /// `W AA[;process:pid]`
+    Exited { code: u8, pid: Option<u64> },
+    /// `X AA[;process:pid]`
+    Terminated { signal_num: u8, pid: Option<u64> },
+    /// `w AA;tid`
+    ThreadExited { code: u8, thread: ThreadId },
+    /// `O <hex>`
+    ConsoleOutput(Vec<u8>),
+    /// `N`
+    NoResumableThreads,
+}
+
+fn stop_reply_signal(i: &[u8]) -> IResult<&[u8], StopReply> {
+    let (i, _) = tag(b"S")(i)?;
+    let (i, signal_num) = two_digit_hex(i)?;
+    Ok((i, StopReply::Signal { signal_num }))
+}
+
+fn stop_reply_halt(i: &[u8]) -> IResult<&[u8], StopReply> {
+    let (i, reason) = halt_reason(i)?;
+    Ok((i, StopReply::Stop(reason)))
+}
+
+fn stop_reply_exited(i: &[u8]) -> IResult<&[u8], StopReply> {
+    let (i, _) = tag(b"W")(i)?;
+    let (i, code) = two_digit_hex(i)?;
+    let (i, pid) = process_pid(i)?;
+    Ok((i, StopReply::Exited { code, pid }))
+}
+
+fn stop_reply_terminated(i: &[u8]) -> IResult<&[u8], StopReply> {
+    let (i, _) = tag(b"X")(i)?;
+    let (i, signal_num) = two_digit_hex(i)?;
+    let (i, pid) = process_pid(i)?;
+    Ok((i, StopReply::Terminated { signal_num, pid }))
+}
+
+fn process_pid(i: &[u8]) -> IResult<&[u8], Option<u64>> {
+    opt(preceded(tag(b";process:"), hex_number))(i)
+}
+
+fn stop_reply_thread_exited(i: &[u8]) -> IResult<&[u8], StopReply> {
+    let (i, _) = tag(b"w")(i)?;
+    let (i, code) = two_digit_hex(i)?;
+    let (i, _) = tag(b";")(i)?;
+    let (i, thread) = thread_id(i)?;
+    Ok((i, StopReply::ThreadExited { code, thread }))
+}
+
+fn stop_reply_console_output(i: &[u8]) -> IResult<&[u8], StopReply> {
+    let (i, _) = tag(b"O")(i)?;
+    let (i, text) = hex_bytes(i)?;
+    Ok((i, StopReply::ConsoleOutput(text)))
+}
+
+fn stop_reply_no_resumable_threads(i: &[u8]) -> IResult<&[u8], StopReply> {
+    let (i, _) = tag(b"N")(i)?;
+    Ok((i, StopReply::NoResumableThreads))
+}
+
 pub fn halt_reason(i: &[u8]) -> IResult<&[u8], HaltReason> {
-    // See GdbConnection::send_stop_reply_packet
     let (i, _) = tag(b"T")(i)?;
     let (i, signal_num) = two_digit_hex(i)?;
-    let (i, _) = tag(b"thread:")(i)?;
-    let (i, thread) = thread_id(i)?;
+    let (i, mut dict) = parse_dict(i)?;
 
-    let (reason, _) = tag(b";")(i)?;
-    let reason = if reason.is_empty() {
-        None
-    } else {
-        let reason =
-            std::str::from_utf8(reason).map_err(|err| Error::new(reason, ErrorKind::Utf8(err)))?;
-        Some(reason.to_owned())
-    };
+    let thread_raw = dict
+        .remove(&b"thread"[..])
+        .ok_or_else(|| Error::new(i, ErrorKind::MissingKey("thread")))?;
+    let thread = parse_complete(&thread_raw, thread_id)?;
+
+    let info = stop_info(i, dict)?;
 
     let reply = HaltReason {
         signal_num,
         thread,
-        reason,
+        info,
     };
 
-    Ok((&[], reply))
+    Ok((i, reply))
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct HaltReason {
-    signal_num: u8,
-    thread: ThreadId,
-    reason: Option<String>,
+    pub signal_num: u8,
+    pub thread: ThreadId,
+    pub info: Vec<StopInfo>,
+}
+
+/// The `n:r` entries that may trail a `T` stop reply, beyond `thread`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum StopInfo {
+    Watch { kind: WatchKind, addr: u64 },
+    SoftwareBreakpoint,
+    HardwareBreakpoint,
+    Library,
+    ReplayLog(Vec<u8>),
+    Fork { kind: ForkKind, child: ThreadId },
+    VforkDone,
+    Exec { filename: Vec<u8> },
+    Core(u64),
+    Register { number: u64, value: Vec<u8> },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WatchKind {
+    Write,
+    Read,
+    Access,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ForkKind {
+    Fork,
+    Vfork,
+}
+
+/// Recognize the known `T`-packet keys, falling back to `<nn>:<regval>`
+/// register entries for anything left over.
+fn stop_info(
+    i: &[u8],
+    mut dict: HashMap<Vec<u8>, Vec<u8>>,
+) -> Result<Vec<StopInfo>, nom::Err<Error>> {
+    let mut info = Vec::new();
+
+    if let Some(value) = dict.remove(&b"watch"[..]) {
+        let addr = parse_complete(&value, hex_number)?;
+        info.push(StopInfo::Watch {
+            kind: WatchKind::Write,
+            addr,
+        });
+    }
+    if let Some(value) = dict.remove(&b"rwatch"[..]) {
+        let addr = parse_complete(&value, hex_number)?;
+        info.push(StopInfo::Watch {
+            kind: WatchKind::Read,
+            addr,
+        });
+    }
+    if let Some(value) = dict.remove(&b"awatch"[..]) {
+        let addr = parse_complete(&value, hex_number)?;
+        info.push(StopInfo::Watch {
+            kind: WatchKind::Access,
+            addr,
+        });
+    }
+    if dict.remove(&b"swbreak"[..]).is_some() {
+        info.push(StopInfo::SoftwareBreakpoint);
+    }
+    if dict.remove(&b"hwbreak"[..]).is_some() {
+        info.push(StopInfo::HardwareBreakpoint);
+    }
+    if dict.remove(&b"library"[..]).is_some() {
+        info.push(StopInfo::Library);
+    }
+    if let Some(value) = dict.remove(&b"replaylog"[..]) {
+        info.push(StopInfo::ReplayLog(value));
+    }
+    if let Some(value) = dict.remove(&b"fork"[..]) {
+        let child = parse_complete(&value, thread_id)?;
+        info.push(StopInfo::Fork {
+            kind: ForkKind::Fork,
+            child,
+        });
+    }
+    if let Some(value) = dict.remove(&b"vfork"[..]) {
+        let child = parse_complete(&value, thread_id)?;
+        info.push(StopInfo::Fork {
+            kind: ForkKind::Vfork,
+            child,
+        });
+    }
+    if dict.remove(&b"vforkdone"[..]).is_some() {
+        info.push(StopInfo::VforkDone);
+    }
+    if let Some(value) = dict.remove(&b"exec"[..]) {
+        let filename = parse_complete(&value, hex_bytes)?;
+        info.push(StopInfo::Exec { filename });
+    }
+    if let Some(value) = dict.remove(&b"core"[..]) {
+        let core = parse_complete(&value, hex_number)?;
+        info.push(StopInfo::Core(core));
+    }
+
+    // Whatever is left must be `<register number>:<register bytes>` pairs.
+    let mut registers = Vec::with_capacity(dict.len());
+    for (key, value) in dict {
+        let number = parse_complete(&key, hex_number)
+            .map_err(|_| Error::new(i, ErrorKind::UnknownStopInfoKey(key.clone())))?;
+        let value = parse_complete(&value, hex_bytes)?;
+        registers.push((number, value));
+    }
+    registers.sort_by_key(|(number, _)| *number);
+    for (number, value) in registers {
+        info.push(StopInfo::Register { number, value });
+    }
+
+    Ok(info)
 }
 
 fn thread_id(i: &[u8]) -> IResult<&[u8], ThreadId> {
@@ -68,8 +271,12 @@ pub enum ThreadId {
     SingleProcess { tid: u64 },
 }
 
+/// Parse `key:value;key:value;...`, as found trailing a `T` stop reply.
 fn parse_dict(i: &[u8]) -> IResult<&[u8], HashMap<Vec<u8>, Vec<u8>>> {
-    let (i, pairs) = many0(pair(take_until("="), take_until(";")))(i)?;
+    let (i, pairs) = many0(terminated(
+        separated_pair(take_until(":"), tag(":"), take_until(";")),
+        tag(";"),
+    ))(i)?;
     let mut map = HashMap::new();
     for (k, v) in pairs {
         map.insert(k.to_owned(), v.to_owned());
@@ -77,10 +284,114 @@ fn parse_dict(i: &[u8]) -> IResult<&[u8], HashMap<Vec<u8>, Vec<u8>>> {
     Ok((i, map))
 }
 
+/// Run `parser` over an already-isolated dict value (e.g. one `parse_dict`
+/// entry) and require it to consume all of `input`, turning a non-empty
+/// remainder into `ErrorKind::TrailingBytes` instead of silently dropping it.
+fn parse_complete<O>(
+    input: &[u8],
+    parser: impl Fn(&[u8]) -> IResult<&[u8], O>,
+) -> Result<O, nom::Err<Error>> {
+    let (rest, value) = parser(input)?;
+    if !rest.is_empty() {
+        return Err(Error::new(rest, ErrorKind::TrailingBytes(rest.to_vec())));
+    }
+    Ok(value)
+}
+
+/// Decode a run of `AA` hex byte pairs, as used by `O` console output and
+/// register/filename values within a `T` stop reply.
+pub(crate) fn hex_bytes(i: &[u8]) -> IResult<&[u8], Vec<u8>> {
+    many0(two_digit_hex)(i)
+}
+
+/// The top-level framing of the RSP wire protocol: acks, the interrupt byte,
+/// and `$`/`%`-delimited bodies.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Frame {
+    /// `+`
+    Ack,
+    /// `-`
+    Nack,
+    /// The out-of-band interrupt byte, 0x03.
+    Interrupt,
+    /// `$<body>#xx`, acknowledged by the receiver.
+    Packet(Vec<u8>),
+    /// `%<body>#xx`, not acknowledged.
+    Notification(Vec<u8>),
+}
+
+pub fn frame(i: &[u8]) -> IResult<&[u8], Frame> {
+    context(
+        "frame",
+        alt((
+            frame_ack,
+            frame_nack,
+            frame_interrupt,
+            frame_packet,
+            frame_notification,
+        )),
+    )(i)
+}
+
+fn frame_ack(i: &[u8]) -> IResult<&[u8], Frame> {
+    let (i, _) = streaming::tag(b"+")(i)?;
+    Ok((i, Frame::Ack))
+}
+
+fn frame_nack(i: &[u8]) -> IResult<&[u8], Frame> {
+    let (i, _) = streaming::tag(b"-")(i)?;
+    Ok((i, Frame::Nack))
+}
+
+fn frame_interrupt(i: &[u8]) -> IResult<&[u8], Frame> {
+    let (i, _) = streaming::tag(&[INTERRUPT_BYTE][..])(i)?;
+    Ok((i, Frame::Interrupt))
+}
+
+const INTERRUPT_BYTE: u8 = 0x03;
+
+fn frame_packet(i: &[u8]) -> IResult<&[u8], Frame> {
+    let (i, body) = packet_body(i)?;
+    Ok((i, Frame::Packet(body)))
+}
+
+fn frame_notification(i: &[u8]) -> IResult<&[u8], Frame> {
+    let (i, body) = notification_body(i)?;
+    Ok((i, Frame::Notification(body)))
+}
+
+const NOTIFICATION_START: u8 = b'%';
+
 pub fn packet_body(i: &[u8]) -> IResult<&[u8], Vec<u8>> {
-    let (i, _) = tag(&[common::PACKET_START][..])(i)?;
+    let (i, body) = framed_payload(i, common::PACKET_START)?;
+
+    // `E`-coded application errors are reply/command semantics: only a
+    // packet can be an error reply, a notification never is.
+    if body.starts_with(b"E") {
+        let body = &body[1..];
+        let (rest, code) = two_digit_hex(body)?;
+        assert!(rest.is_empty());
+        return Err(Error::new(body, ErrorKind::App(code)));
+    }
+
+    let body = expand_body(body).map_err(|err| Error::new(body, ErrorKind::ExpandBody(err)))?;
+    Ok((i, body))
+}
+
+/// `%<body>#xx`: framed identically to a packet, but never acknowledged and
+/// never an `E`-coded application error.
+fn notification_body(i: &[u8]) -> IResult<&[u8], Vec<u8>> {
+    let (i, body) = framed_payload(i, NOTIFICATION_START)?;
+    let body = expand_body(body).map_err(|err| Error::new(body, ErrorKind::ExpandBody(err)))?;
+    Ok((i, body))
+}
+
+/// The framing shared by packets and notifications: `<start><body>#<checksum>`,
+/// with the checksum verified but the body not yet un-escaped/un-RLE'd.
+fn framed_payload(i: &[u8], start: u8) -> IResult<&[u8], &[u8]> {
+    let (i, _) = streaming::tag(&[start][..])(i)?;
 
-    let (i, body) = take_until(&[common::CHECKSUM_START][..])(i)?;
+    let (i, body) = streaming::take_until(&[common::CHECKSUM_START][..])(i)?;
     let i = &i[1..]; // take off #
 
     let (i, expected) = checksum(i)?;
@@ -92,15 +403,99 @@ pub fn packet_body(i: &[u8]) -> IResult<&[u8], Vec<u8>> {
         ));
     }
 
-    if body.starts_with(b"E") {
-        let body = &body[1..];
-        let (rest, code) = two_digit_hex(body)?;
-        assert!(rest.is_empty());
-        return Err(Error::new(body, ErrorKind::App(code)));
+    Ok((i, body))
+}
+
+/// Frame a packet body for transmission: `$<escaped+rle body>#<checksum>`.
+///
+/// This is the inverse of [`packet_body`]/[`expand_body`].
+pub fn encode_packet(body: &[u8]) -> Vec<u8> {
+    let encoded = encode_body(body);
+    let checksum = compute_checksum(&encoded);
+
+    let mut out = Vec::with_capacity(encoded.len() + 4);
+    out.push(common::PACKET_START);
+    out.extend_from_slice(&encoded);
+    out.push(common::CHECKSUM_START);
+    out.extend(format!("{:02x}", checksum).into_bytes());
+    out
+}
+
+/// Escape then run-length encode a body, without framing it as a packet.
+///
+/// This is the inverse of [`expand_body`].
+fn encode_body(body: &[u8]) -> Vec<u8> {
+    run_length_encode(&escape_body(body))
+}
+
+/// Escape any of `# $ } *` as [`ESCAPE_INDICATOR`] followed by `byte ^ 0x20`.
+fn escape_body(body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len());
+    for &byte in body {
+        if matches!(byte, b'#' | b'$' | b'}' | b'*') {
+            out.push(ESCAPE_INDICATOR);
+            out.push(byte ^ 0x20);
+        } else {
+            out.push(byte);
+        }
     }
+    out
+}
 
-    let body = expand_body(body).map_err(|err| Error::new(body, ErrorKind::ExpandBody(err)))?;
-    Ok((i, body))
+/// Minimum run length worth encoding: shorter runs cost at least as many
+/// bytes as they'd save.
+const MIN_RUN_LENGTH: usize = 3;
+/// Largest run length a single count byte, `(N - 1) + 29`, can represent.
+const MAX_RUN_LENGTH: usize = 98;
+
+/// Collapse runs of identical bytes as `<byte>*<count-char>`, where the count
+/// character is `(N - 1) + 29`.
+///
+/// Mirrors the run-length half of [`expand_body`]: the decoder subtracts 28
+/// from the count byte and then accounts for the byte already emitted once.
+fn run_length_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut idx = 0;
+    while idx < data.len() {
+        let byte = data[idx];
+        let mut run_len = 1;
+        while idx + run_len < data.len() && data[idx + run_len] == byte {
+            run_len += 1;
+        }
+
+        if run_len >= MIN_RUN_LENGTH {
+            let run_len = capped_run_length(run_len);
+            if run_len >= MIN_RUN_LENGTH {
+                out.push(byte);
+                out.push(RUN_LENGTH_INDICATOR);
+                out.push((run_len - 1) as u8 + 29);
+                idx += run_len;
+                continue;
+            }
+        }
+
+        out.push(byte);
+        idx += 1;
+    }
+    out
+}
+
+/// Shorten a run so its count byte never lands on `#` or `$`, capping at
+/// [`MAX_RUN_LENGTH`] along the way.
+///
+/// The caller starts a fresh run at whatever is left over, so shortening here
+/// is enough to satisfy the constraint overall.
+fn capped_run_length(run_len: usize) -> usize {
+    let mut run_len = run_len.min(MAX_RUN_LENGTH);
+    while run_len >= MIN_RUN_LENGTH {
+        let count_byte = (run_len - 1) as u8 + 29;
+        if count_byte == b'#' || count_byte == b'$' {
+            run_len -= 1;
+        } else {
+            break;
+        }
+    }
+    run_len
 }
 
 fn expand_body(body: &[u8]) -> Result<Vec<u8>, ExpandError> {
@@ -144,8 +539,24 @@ pub enum ExpandError {
 const ESCAPE_INDICATOR: u8 = b'}';
 const RUN_LENGTH_INDICATOR: u8 = b'*';
 
+/// The two checksum digits trailing `#`: framing, not content, so (unlike
+/// [`two_digit_hex`]) this reads off the growing wire buffer and must ask
+/// for more bytes via `Incomplete` rather than erroring when only one digit
+/// has arrived so far.
 fn checksum(i: &[u8]) -> IResult<&[u8], u8> {
-    two_digit_hex(i)
+    let (i, d1) = streaming_hex_digit(i)?;
+    let (i, d2) = streaming_hex_digit(i)?;
+    Ok((i, (d1 << 4) + d2))
+}
+
+fn streaming_hex_digit(i: &[u8]) -> IResult<&[u8], u8> {
+    let (i, digit) = streaming::take(1_usize)(i)?;
+    let digit = digit[0] as char;
+
+    digit.to_digit(HEX_RADIX).map_or_else(
+        || Err(Error::new(i, ErrorKind::ExpectedHexDigit(digit))),
+        |digit| Ok((i, digit.try_into().unwrap())),
+    )
 }
 
 fn two_digit_hex(i: &[u8]) -> IResult<&[u8], u8> {
@@ -193,20 +604,27 @@ pub enum ErrorKind {
     FailedChecksum { expected: u8, actual: u8 },
     /// Failed to expand body: {0}
     ExpandBody(ExpandError),
-    /// Failed to parse as utf-8: {0}
-    Utf8(std::str::Utf8Error),
     /// Application level error. Code: {0}
     App(u8),
+    /// Missing required key: {0}
+    MissingKey(&'static str),
+    /// Unrecognized stop-info key: {0:?}
+    UnknownStopInfoKey(Vec<u8>),
+    /// Trailing unparsed bytes: {0:?}
+    TrailingBytes(Vec<u8>),
+    /// Reply was truncated: parsing ran out of input before it could decide
+    /// whether the reply was well-formed
+    Truncated,
     /// Nom error: {0:?}
     Nom(nom::error::ErrorKind),
 }
 
 impl Error {
-    fn new(input: &[u8], kind: ErrorKind) -> nom::Err<Self> {
+    pub(crate) fn new(input: &[u8], kind: ErrorKind) -> nom::Err<Self> {
         Self::new_inner(input, kind).into()
     }
 
-    fn new_inner(input: &[u8], kind: ErrorKind) -> Self {
+    pub(crate) fn new_inner(input: &[u8], kind: ErrorKind) -> Self {
         Self {
             input: input.into(),
             kind,
@@ -214,6 +632,19 @@ impl Error {
             causes: Vec::new(),
         }
     }
+
+    /// Flatten a `nom::Err` into a plain `Error`, for callers (e.g.
+    /// `super::target`) that parse an already-complete reply and don't want
+    /// to handle `nom::Err::Incomplete` separately: over a fully-materialized
+    /// slice, `Incomplete` just means "not well-formed", same as
+    /// `Error`/`Failure`. (The framing parsers, [`frame`] and [`packet_body`],
+    /// use `Incomplete` to ask for more wire bytes and aren't meant for this.)
+    pub(crate) fn from_nom_err(input: &[u8], err: nom::Err<Self>) -> Self {
+        match err {
+            nom::Err::Error(err) | nom::Err::Failure(err) => err,
+            nom::Err::Incomplete(_) => Self::new_inner(input, ErrorKind::Truncated),
+        }
+    }
 }
 
 impl fmt::Display for Error {
@@ -268,13 +699,134 @@ mod tests {
             HaltReason {
                 signal_num: 0,
                 thread: ThreadId::SingleProcess { tid: 0x0029_164b },
-                reason: None,
+                info: vec![],
+            },
+        );
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_reply_signal() -> eyre::Result<()> {
+        let actual = stop_reply(b"S05")?;
+        let expected = (&[][..], StopReply::Signal { signal_num: 5 });
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_reply_stop_with_swbreak() -> eyre::Result<()> {
+        let actual = stop_reply(b"T05thread:1;swbreak:;")?;
+        let expected = (
+            &[][..],
+            StopReply::Stop(HaltReason {
+                signal_num: 5,
+                thread: ThreadId::SingleProcess { tid: 1 },
+                info: vec![StopInfo::SoftwareBreakpoint],
+            }),
+        );
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_reply_stop_with_watch() -> eyre::Result<()> {
+        let actual = stop_reply(b"T05thread:1;watch:7ffff7a9c000;")?;
+        let expected = (
+            &[][..],
+            StopReply::Stop(HaltReason {
+                signal_num: 5,
+                thread: ThreadId::SingleProcess { tid: 1 },
+                info: vec![StopInfo::Watch {
+                    kind: WatchKind::Write,
+                    addr: 0x7fff_f7a9_c000,
+                }],
+            }),
+        );
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_reply_stop_with_register() -> eyre::Result<()> {
+        let actual = stop_reply(b"T05thread:1;00:0001020304050607;")?;
+        let expected = (
+            &[][..],
+            StopReply::Stop(HaltReason {
+                signal_num: 5,
+                thread: ThreadId::SingleProcess { tid: 1 },
+                info: vec![StopInfo::Register {
+                    number: 0,
+                    value: vec![0, 1, 2, 3, 4, 5, 6, 7],
+                }],
+            }),
+        );
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_reply_exited() -> eyre::Result<()> {
+        let actual = stop_reply(b"W00")?;
+        let expected = (&[][..], StopReply::Exited { code: 0, pid: None });
+        assert_eq!(expected, actual);
+
+        let actual = stop_reply(b"W00;process:1234")?;
+        let expected = (
+            &[][..],
+            StopReply::Exited {
+                code: 0,
+                pid: Some(0x1234),
+            },
+        );
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_reply_terminated() -> eyre::Result<()> {
+        let actual = stop_reply(b"X0b")?;
+        let expected = (
+            &[][..],
+            StopReply::Terminated {
+                signal_num: 0x0b,
+                pid: None,
             },
         );
         assert_eq!(expected, actual);
         Ok(())
     }
 
+    #[test]
+    fn test_stop_reply_thread_exited() -> eyre::Result<()> {
+        let actual = stop_reply(b"w00;1")?;
+        let expected = (
+            &[][..],
+            StopReply::ThreadExited {
+                code: 0,
+                thread: ThreadId::SingleProcess { tid: 1 },
+            },
+        );
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_reply_console_output() -> eyre::Result<()> {
+        let actual = stop_reply(b"O666f6f")?;
+        let expected = (&[][..], StopReply::ConsoleOutput(b"foo".to_vec()));
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_reply_no_resumable_threads() -> eyre::Result<()> {
+        let actual = stop_reply(b"N")?;
+        let expected = (&[][..], StopReply::NoResumableThreads);
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
     #[test]
     fn test_hex_number() -> eyre::Result<()> {
         let data: &[(_, (_, u64))] = &[
@@ -353,4 +905,95 @@ mod tests {
         assert_eq!(expected, actual);
         Ok(())
     }
+
+    #[test]
+    fn test_frame_ack_nack_interrupt() -> eyre::Result<()> {
+        assert_eq!((&b""[..], Frame::Ack), frame(b"+")?);
+        assert_eq!((&b""[..], Frame::Nack), frame(b"-")?);
+        assert_eq!((&b""[..], Frame::Interrupt), frame(&[0x03])?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_packet() -> eyre::Result<()> {
+        let actual = frame(b"$foo#44")?;
+        let expected = (&b""[..], Frame::Packet(b"foo".to_vec()));
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_notification() -> eyre::Result<()> {
+        let actual = frame(b"%foo#44")?;
+        let expected = (&b""[..], Frame::Notification(b"foo".to_vec()));
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_notification_body_starting_with_e_is_not_an_app_error() -> eyre::Result<()> {
+        // Only a packet reply can be an `E`-coded application error; a
+        // notification whose body happens to start with `E` is just data.
+        let actual = frame(b"%E01#a6")?;
+        let expected = (&b""[..], Frame::Notification(b"E01".to_vec()));
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_packet() -> eyre::Result<()> {
+        let actual = encode_packet(b"foo");
+        let expected = b"$foo#44".to_vec();
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_body_round_trip() -> eyre::Result<()> {
+        let cases: &[&[u8]] = &[
+            b"",
+            b"foo",
+            b"foo_XXXXX_bar",
+            b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            b"###$$$}}}****",
+            b"aaaaaaa",  // 7-run: unadjusted count byte would collide with '#'
+            b"aaaaaaaa", // 8-run: unadjusted count byte would collide with '$'
+            &[0u8, 1, 2, 255, 254, 0, 0, 0],
+        ];
+
+        for case in cases {
+            let encoded = encode_body(case);
+            let decoded = expand_body(&encoded).map_err(|err| eyre::eyre!(err))?;
+            assert_eq!(*case, decoded.as_slice());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_body_round_trip_random_bytes() -> eyre::Result<()> {
+        // Neither proptest nor quickcheck is a dependency elsewhere in this
+        // tree, so rather than pull one in for a single test, sweep a wide
+        // space of arbitrary byte strings with a small deterministic PRNG:
+        // this is not as thorough as a real shrinking property test, but it
+        // does exercise lengths/byte values this module's author wouldn't
+        // think to hand-pick, on top of the fixed cases above.
+        let mut state = 0x2545F4914F6CDD1Du64;
+        let mut next_u64 = || {
+            // xorshift64*
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            state.wrapping_mul(0x2545F4914F6CDD1D)
+        };
+
+        for _ in 0..1000 {
+            let len = (next_u64() % 64) as usize;
+            let case: Vec<u8> = (0..len).map(|_| (next_u64() % 256) as u8).collect();
+
+            let encoded = encode_body(&case);
+            let decoded = expand_body(&encoded).map_err(|err| eyre::eyre!(err))?;
+            assert_eq!(case, decoded);
+        }
+        Ok(())
+    }
 }
\ No newline at end of file
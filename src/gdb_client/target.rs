@@ -0,0 +1,421 @@
+//! A [`Target`] abstracts the operations the protocol layer needs from
+//! whatever is actually running the inferior. This lets the decoding/encoding
+//! logic in [`super::parser`] drive either a live gdbserver/LLDB stub over
+//! `GdbConnection` or an in-process emulator, without caring which.
+
+use std::{collections::HashMap, fmt};
+
+use super::connection::SyncConnection;
+use super::parser::{self, StopReply};
+
+pub type RegisterNumber = u64;
+pub type RegisterValue = Vec<u8>;
+pub type Registers = HashMap<RegisterNumber, RegisterValue>;
+
+/// Everything the protocol layer needs to drive a debuggee.
+pub trait Target {
+    type Error: std::error::Error + 'static;
+
+    fn read_registers(&mut self) -> Result<Registers, Self::Error>;
+    fn write_registers(&mut self, registers: &Registers) -> Result<(), Self::Error>;
+    fn read_memory(&mut self, addr: u64, len: usize) -> Result<Vec<u8>, Self::Error>;
+    fn write_memory(&mut self, addr: u64, data: &[u8]) -> Result<(), Self::Error>;
+    fn step(&mut self) -> Result<StopReply, Self::Error>;
+    /// Named `continue_`: `continue` is a reserved word.
+    fn continue_(&mut self) -> Result<StopReply, Self::Error>;
+    fn insert_breakpoint(&mut self, addr: u64) -> Result<(), Self::Error>;
+    fn halt_reason(&mut self) -> Result<StopReply, Self::Error>;
+}
+
+/// Speaks the RSP over a `super::connection::SyncConnection`, e.g.
+/// `super::connection::GdbConnection`.
+pub struct RspTarget<C> {
+    connection: C,
+}
+
+impl<C> RspTarget<C> {
+    pub fn new(connection: C) -> Self {
+        Self { connection }
+    }
+}
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum RspTargetError<E: std::error::Error> {
+    /// connection error: {0}
+    Connection(E),
+    /// failed to parse reply: {0}
+    Parse(parser::Error),
+    /// {0}
+    Unsupported(&'static str),
+}
+
+impl<C> Target for RspTarget<C>
+where
+    C: SyncConnection,
+{
+    type Error = RspTargetError<C::Error>;
+
+    fn read_registers(&mut self) -> Result<Registers, Self::Error> {
+        // The `g` reply is a target-description-dependent blob of raw
+        // register bytes; splitting it into individual registers needs the
+        // active XML target description, which isn't modeled yet. Error
+        // instead of returning registers we didn't actually decode.
+        Err(RspTargetError::Unsupported(
+            "read_registers: general register layout is target-description-dependent and not implemented yet",
+        ))
+    }
+
+    fn write_registers(&mut self, _registers: &Registers) -> Result<(), Self::Error> {
+        Err(RspTargetError::Unsupported(
+            "write_registers: general register layout is target-description-dependent and not implemented yet",
+        ))
+    }
+
+    fn read_memory(&mut self, addr: u64, len: usize) -> Result<Vec<u8>, Self::Error> {
+        let packet = format!("m{:x},{:x}", addr, len);
+        let reply = self
+            .connection
+            .send_packet(packet.as_bytes())
+            .map_err(RspTargetError::Connection)?;
+        decode_hex_bytes(&reply).map_err(RspTargetError::Parse)
+    }
+
+    fn write_memory(&mut self, addr: u64, data: &[u8]) -> Result<(), Self::Error> {
+        let mut packet = format!("M{:x},{:x}:", addr, data.len());
+        for byte in data {
+            packet.push_str(&format!("{:02x}", byte));
+        }
+        self.connection
+            .send_packet(packet.as_bytes())
+            .map_err(RspTargetError::Connection)?;
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<StopReply, Self::Error> {
+        let reply = self
+            .connection
+            .send_packet(b"s")
+            .map_err(RspTargetError::Connection)?;
+        let (_, reply) = parser::stop_reply(&reply)
+            .map_err(|err| RspTargetError::Parse(parser::Error::from_nom_err(&reply, err)))?;
+        Ok(reply)
+    }
+
+    fn continue_(&mut self) -> Result<StopReply, Self::Error> {
+        let reply = self
+            .connection
+            .send_packet(b"c")
+            .map_err(RspTargetError::Connection)?;
+        let (_, reply) = parser::stop_reply(&reply)
+            .map_err(|err| RspTargetError::Parse(parser::Error::from_nom_err(&reply, err)))?;
+        Ok(reply)
+    }
+
+    fn insert_breakpoint(&mut self, addr: u64) -> Result<(), Self::Error> {
+        let packet = format!("Z0,{:x},1", addr);
+        self.connection
+            .send_packet(packet.as_bytes())
+            .map_err(RspTargetError::Connection)?;
+        Ok(())
+    }
+
+    fn halt_reason(&mut self) -> Result<StopReply, Self::Error> {
+        let reply = self
+            .connection
+            .send_packet(b"?")
+            .map_err(RspTargetError::Connection)?;
+        let (_, reply) = parser::stop_reply(&reply)
+            .map_err(|err| RspTargetError::Parse(parser::Error::from_nom_err(&reply, err)))?;
+        Ok(reply)
+    }
+}
+
+/// Decode an `m`-reply's `AA...` hex body, reusing [`parser::hex_bytes`] so a
+/// malformed/odd-length reply is rejected rather than silently truncated.
+fn decode_hex_bytes(reply: &[u8]) -> Result<Vec<u8>, parser::Error> {
+    let (rest, bytes) =
+        parser::hex_bytes(reply).map_err(|err| parser::Error::from_nom_err(reply, err))?;
+    if !rest.is_empty() {
+        return Err(parser::Error::new_inner(
+            rest,
+            parser::ErrorKind::TrailingBytes(rest.to_vec()),
+        ));
+    }
+    Ok(bytes)
+}
+
+/// Exposes a single register file, split out from [`MemoryAccess`] the same
+/// way `emulator-hal` splits register and memory access into independent
+/// traits an emulator can implement only the subset of.
+pub trait RegisterAccess {
+    type Error: std::error::Error + 'static;
+
+    fn read_registers(&mut self) -> Result<Registers, Self::Error>;
+    fn write_registers(&mut self, registers: &Registers) -> Result<(), Self::Error>;
+}
+
+/// Exposes the address space, split out from [`RegisterAccess`].
+pub trait MemoryAccess {
+    type Error: std::error::Error + 'static;
+
+    fn read_memory(&mut self, addr: u64, len: usize) -> Result<Vec<u8>, Self::Error>;
+    fn write_memory(&mut self, addr: u64, data: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Advances execution and manages breakpoints in-process.
+pub trait Steppable {
+    type Error: std::error::Error + 'static;
+
+    /// Execute a single instruction and report why it stopped.
+    fn step(&mut self) -> Result<StopReply, Self::Error>;
+    /// Run until the next breakpoint and report why it stopped.
+    fn continue_(&mut self) -> Result<StopReply, Self::Error>;
+    fn insert_breakpoint(&mut self, addr: u64) -> Result<(), Self::Error>;
+}
+
+/// Wraps an in-process emulator exposing [`RegisterAccess`], [`MemoryAccess`]
+/// and [`Steppable`] hooks, and drives it as a [`Target`].
+pub struct EmulatorTarget<E> {
+    emulator: E,
+    /// `halt_reason` can be queried outside of a `step`/`continue_` call, so
+    /// we remember the most recent stop.
+    last_stop: Option<StopReply>,
+}
+
+impl<E> EmulatorTarget<E> {
+    pub fn new(emulator: E) -> Self {
+        Self {
+            emulator,
+            last_stop: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EmulatorTargetError<R, M, S> {
+    Registers(R),
+    Memory(M),
+    Step(S),
+    /// `halt_reason` was called before the emulator ever stopped.
+    NeverStopped,
+}
+
+impl<R: fmt::Display, M: fmt::Display, S: fmt::Display> fmt::Display
+    for EmulatorTargetError<R, M, S>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Registers(err) => write!(f, "register access failed: {}", err),
+            Self::Memory(err) => write!(f, "memory access failed: {}", err),
+            Self::Step(err) => write!(f, "step/breakpoint failed: {}", err),
+            Self::NeverStopped => write!(f, "halt_reason was called before the emulator stopped"),
+        }
+    }
+}
+
+impl<R, M, S> std::error::Error for EmulatorTargetError<R, M, S>
+where
+    R: std::error::Error + 'static,
+    M: std::error::Error + 'static,
+    S: std::error::Error + 'static,
+{
+}
+
+impl<E> Target for EmulatorTarget<E>
+where
+    E: RegisterAccess + MemoryAccess + Steppable,
+{
+    type Error = EmulatorTargetError<
+        <E as RegisterAccess>::Error,
+        <E as MemoryAccess>::Error,
+        <E as Steppable>::Error,
+    >;
+
+    fn read_registers(&mut self) -> Result<Registers, Self::Error> {
+        self.emulator
+            .read_registers()
+            .map_err(EmulatorTargetError::Registers)
+    }
+
+    fn write_registers(&mut self, registers: &Registers) -> Result<(), Self::Error> {
+        self.emulator
+            .write_registers(registers)
+            .map_err(EmulatorTargetError::Registers)
+    }
+
+    fn read_memory(&mut self, addr: u64, len: usize) -> Result<Vec<u8>, Self::Error> {
+        self.emulator
+            .read_memory(addr, len)
+            .map_err(EmulatorTargetError::Memory)
+    }
+
+    fn write_memory(&mut self, addr: u64, data: &[u8]) -> Result<(), Self::Error> {
+        self.emulator
+            .write_memory(addr, data)
+            .map_err(EmulatorTargetError::Memory)
+    }
+
+    fn step(&mut self) -> Result<StopReply, Self::Error> {
+        let stop = self.emulator.step().map_err(EmulatorTargetError::Step)?;
+        self.last_stop = Some(stop.clone());
+        Ok(stop)
+    }
+
+    fn continue_(&mut self) -> Result<StopReply, Self::Error> {
+        let stop = self
+            .emulator
+            .continue_()
+            .map_err(EmulatorTargetError::Step)?;
+        self.last_stop = Some(stop.clone());
+        Ok(stop)
+    }
+
+    fn insert_breakpoint(&mut self, addr: u64) -> Result<(), Self::Error> {
+        self.emulator
+            .insert_breakpoint(addr)
+            .map_err(EmulatorTargetError::Step)
+    }
+
+    fn halt_reason(&mut self) -> Result<StopReply, Self::Error> {
+        self.last_stop
+            .clone()
+            .ok_or(EmulatorTargetError::NeverStopped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Replies with canned packet bodies, one per `send_packet` call, in
+    /// order. Ignores the request body: these tests only exercise decoding.
+    #[derive(Default)]
+    struct MockConnection {
+        replies: std::collections::VecDeque<Vec<u8>>,
+    }
+
+    impl MockConnection {
+        fn replying_with(replies: impl IntoIterator<Item = &'static [u8]>) -> Self {
+            Self {
+                replies: replies.into_iter().map(|r| r.to_vec()).collect(),
+            }
+        }
+    }
+
+    impl SyncConnection for MockConnection {
+        type Error = Infallible;
+
+        fn send_packet(&mut self, _body: &[u8]) -> Result<Vec<u8>, Self::Error> {
+            Ok(self.replies.pop_front().expect("unexpected send_packet call"))
+        }
+    }
+
+    #[test]
+    fn read_registers_is_unsupported() {
+        let mut target = RspTarget::new(MockConnection::default());
+        assert!(matches!(
+            target.read_registers(),
+            Err(RspTargetError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn write_registers_is_unsupported() {
+        let mut target = RspTarget::new(MockConnection::default());
+        assert!(matches!(
+            target.write_registers(&Registers::new()),
+            Err(RspTargetError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn read_memory_decodes_hex_reply() -> eyre::Result<()> {
+        let mut target = RspTarget::new(MockConnection::replying_with([&b"68656c6c6f"[..]]));
+        let actual = target.read_memory(0, 5)?;
+        assert_eq!(b"hello".to_vec(), actual);
+        Ok(())
+    }
+
+    #[test]
+    fn read_memory_rejects_odd_length_reply() {
+        let mut target = RspTarget::new(MockConnection::replying_with([&b"abc"[..]]));
+        assert!(matches!(
+            target.read_memory(0, 2),
+            Err(RspTargetError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn step_parses_the_stop_reply() -> eyre::Result<()> {
+        let mut target = RspTarget::new(MockConnection::replying_with([&b"S05"[..]]));
+        let actual = target.step()?;
+        assert_eq!(StopReply::Signal { signal_num: 5 }, actual);
+        Ok(())
+    }
+
+    /// An in-process stand-in for [`RegisterAccess`]/[`MemoryAccess`]/
+    /// [`Steppable`] that never fails and returns a fixed stop reply.
+    #[derive(Default)]
+    struct MockEmulator;
+
+    impl RegisterAccess for MockEmulator {
+        type Error = Infallible;
+
+        fn read_registers(&mut self) -> Result<Registers, Self::Error> {
+            Ok(Registers::new())
+        }
+
+        fn write_registers(&mut self, _registers: &Registers) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl MemoryAccess for MockEmulator {
+        type Error = Infallible;
+
+        fn read_memory(&mut self, _addr: u64, len: usize) -> Result<Vec<u8>, Self::Error> {
+            Ok(vec![0; len])
+        }
+
+        fn write_memory(&mut self, _addr: u64, _data: &[u8]) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl Steppable for MockEmulator {
+        type Error = Infallible;
+
+        fn step(&mut self) -> Result<StopReply, Self::Error> {
+            Ok(StopReply::Signal { signal_num: 5 })
+        }
+
+        fn continue_(&mut self) -> Result<StopReply, Self::Error> {
+            Ok(StopReply::Signal { signal_num: 5 })
+        }
+
+        fn insert_breakpoint(&mut self, _addr: u64) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn emulator_target_halt_reason_before_any_stop_errors() {
+        let mut target = EmulatorTarget::new(MockEmulator);
+        assert!(matches!(
+            target.halt_reason(),
+            Err(EmulatorTargetError::NeverStopped)
+        ));
+    }
+
+    #[test]
+    fn emulator_target_halt_reason_remembers_the_last_stop() -> eyre::Result<()> {
+        let mut target = EmulatorTarget::new(MockEmulator);
+        target.step()?;
+        assert_eq!(StopReply::Signal { signal_num: 5 }, target.halt_reason()?);
+        Ok(())
+    }
+}